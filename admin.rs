@@ -0,0 +1,36 @@
+//! The token's admin surface as an ink! trait definition, so multisigs and
+//! governor contracts can be written against any deployment of this crate
+//! through the `Erc20Admin::*` selectors.
+
+use crate::traits::{Balance, Timestamp};
+use crate::{Error, FeeCurve, TimelockOperation};
+use ink::primitives::AccountId;
+
+/// Each message needs the same role as the token's message of the same name.
+/// Upgrades have no message of their own: they are a `TimelockOperation`
+/// only a passed proposal can schedule.
+#[ink::trait_definition]
+pub trait Erc20Admin {
+    #[ink(message)]
+    fn mint(&mut self, to: AccountId, value: Balance) -> Result<(), Error>;
+
+    #[ink(message)]
+    fn pause(&mut self) -> Result<(), Error>;
+
+    #[ink(message)]
+    fn unpause(&mut self) -> Result<(), Error>;
+
+    /// `None` turns transfer fees off.
+    #[ink(message)]
+    fn set_fee_curve(&mut self, curve: Option<FeeCurve>) -> Result<(), Error>;
+
+    /// Queues `operation` behind the timelock, returns the operation id.
+    #[ink(message)]
+    fn schedule(&mut self, operation: TimelockOperation, delay: Timestamp) -> Result<u64, Error>;
+
+    #[ink(message)]
+    fn execute_scheduled(&mut self, operation_id: u64) -> Result<(), Error>;
+
+    #[ink(message)]
+    fn cancel_scheduled(&mut self, operation_id: u64) -> Result<(), Error>;
+}
@@ -0,0 +1,27 @@
+#!/bin/sh
+# Fuzzes the dispatch target under every combination of the contract's
+# compile-time features, for SECONDS (default 60) each.
+#
+# Run from the repository root: `fuzz/feature-combinations.sh [SECONDS]`.
+set -e
+
+seconds=${1:-60}
+features="balance-proofs suppress-zero-value-transfer-events suppress-spend-approval-events"
+count=$(echo $features | wc -w)
+
+combination=0
+while [ $combination -lt $((1 << count)) ]; do
+    enabled=""
+    bit=0
+    for feature in $features; do
+        if [ $((combination >> bit & 1)) -eq 1 ]; then
+            enabled="$enabled,$feature"
+        fi
+        bit=$((bit + 1))
+    done
+    enabled=${enabled#,}
+
+    echo "fuzzing with features: ${enabled:-none}"
+    cargo +nightly fuzz run dispatch --features "$enabled" -- -max_total_time="$seconds"
+    combination=$((combination + 1))
+done
@@ -0,0 +1,335 @@
+//! Feeds arbitrary message sequences from arbitrary callers into the contract
+//! in the off-chain environment, after switching on an arbitrary combination
+//! of extensions. Every message has to either succeed or return an error,
+//! and after each one the supply invariants in `assert_invariants` must hold.
+//!
+//! Run from the repository root with `cargo +nightly fuzz run dispatch`. The
+//! compile-time features are forwarded, e.g. `--features balance-proofs`, and
+//! `fuzz/feature-combinations.sh` runs the target under each combination.
+#![no_main]
+
+use arbitrary::Arbitrary;
+use erc20::governor::{GovernorConfig, VoteType};
+use erc20::psp22::{PSP22Error, PSP22};
+use erc20::{ClaimBucket, Erc20, Error, FeeCurve, FeeDiscountTier, PairFees, TimelockOperation};
+use ink::env::{test, DefaultEnvironment};
+use ink::primitives::{AccountId, Hash};
+use libfuzzer_sys::fuzz_target;
+
+/// Number of distinct accounts callers and counterparties are picked from.
+const ACCOUNTS: u8 = 4;
+
+/// Setup step run by the deployer, which holds every role, before the calls.
+#[derive(Arbitrary, Debug)]
+enum Extension {
+    FeeCurve { base_bps: u16, max_bps: u16, target_volume: u128, recipient: u8 },
+    BurnSink { sink: u8 },
+    BurnAddress { address: u8 },
+    PublicSnapshots,
+    AllowanceSnapshots,
+    Snapshot,
+    LargeMintThreshold { threshold: u128 },
+    Timelock { delay: u32 },
+    Governor { voting_period: u32, proposal_threshold: u128, quorum: u128 },
+    Paused,
+}
+
+#[derive(Arbitrary, Debug)]
+enum Message {
+    Transfer { to: u8, value: u128 },
+    TransferUnchecked { to: u8, value: u128 },
+    BatchTransfer { legs: Vec<(u8, u128)> },
+    BatchTransferFrom { from: u8, legs: Vec<(u8, u128)> },
+    Approve { spender: u8, value: u128 },
+    IncreaseAllowance { spender: u8, delta: u128 },
+    DecreaseAllowance { spender: u8, delta: u128 },
+    TransferFrom { from: u8, to: u8, value: u128 },
+    SubApprove { owner: u8, delegate: u8, value: u128 },
+    SetSubDelegationAllowed { allowed: bool },
+    TransferWithDeadline { to: u8, value: u128, deadline: u64 },
+    TransferWithIdempotencyKey { to: u8, value: u128, key: u8 },
+    SetRecipientGuard { threshold: Option<u128> },
+    RegisterRecipient { recipient: u8 },
+    Burn { value: u128 },
+    BurnFrom { from: u8, value: u128 },
+    SetBurnAddress { address: u8, enabled: bool },
+    Mint { to: u8, value: u128 },
+    Airdrop { airdrop_id: u8, cursor: u8, legs: Vec<(u8, u128)>, from_treasury: bool },
+    ScheduleMint { to: u8, value: u128, delay: u32 },
+    ExecuteScheduled { operation_id: u8 },
+    Pause,
+    SetFeeCurve { base_bps: u16, max_bps: u16, target_volume: u128, recipient: u8 },
+    SetPairFees { pair: u8, fees: Option<(u16, u16)> },
+    SetFeeDiscountTiers { tiers: Vec<(u128, u16)> },
+    TransferExactOut { to: u8, net_value: u128 },
+    SetOperator { operator: u8, approved: bool },
+    OperatorTransfer { from: u8, to: u8, value: u128 },
+    Snapshot,
+    AttestSubBalances { snapshot_id: u8, total: u128 },
+    CreateMandate { merchant: u8, max_per_charge: u128, period: u32 },
+    ChargeMandate { mandate_id: u8, value: u128 },
+    RevokeMandate { mandate_id: u8 },
+    Delegate { delegatee: u8 },
+    CreateVesting { beneficiary: u8, total: u128, start: u32, cliff: u32, duration: u32, revocable: bool },
+    SetVestingGrantor { grantor: u8, approved: bool },
+    Release,
+    RevokeVesting { schedule_id: u8 },
+    CreateStream { recipient: u8, deposit: u128, start: u32, stop: u32 },
+    CreateWeightedStream { beneficiaries: Vec<(u8, u32)>, deposit: u128, start: u32, stop: u32 },
+    WithdrawFromStream { stream_id: u8, value: u128 },
+    CancelStream { stream_id: u8 },
+    TransferStream { stream_id: u8, to: u8 },
+    ClaimAll { stream_ids: Vec<u8> },
+    SetClaimDelegate { delegate: Option<u8> },
+    ClaimAllFor { holder: u8, stream_ids: Vec<u8> },
+    SetClaimDeadline { stream_id: u8, deadline: Option<u32> },
+    SweepUnclaimed { stream_id: u8 },
+    CreateEscrow { payee: u8, arbiter: u8, amount: u128 },
+    ReleaseEscrow { escrow_id: u8 },
+    RefundEscrow { escrow_id: u8 },
+    Propose,
+    CastVote { proposal_id: u8, support: u8 },
+    Execute { proposal_id: u8 },
+    Unpause,
+    AdvanceTime { millis: u32 },
+}
+
+#[derive(Arbitrary, Debug)]
+struct Call {
+    caller: u8,
+    message: Message,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    initial_supply: u128,
+    cap: Option<u128>,
+    extensions: Vec<Extension>,
+    calls: Vec<Call>,
+}
+
+fn account(index: u8) -> AccountId {
+    AccountId::from([index % ACCOUNTS; 32])
+}
+
+fn fee_curve(base_bps: u16, max_bps: u16, target_volume: u128, recipient: u8) -> FeeCurve {
+    FeeCurve {
+        epoch_length: 1_000,
+        base_bps,
+        max_bps,
+        target_volume,
+        recipient: account(recipient)
+    }
+}
+
+fn enable(contract: &mut Erc20, extension: Extension) {
+    // Invalid parameters are rejected like any other call and simply leave
+    // the extension off.
+    let _ = match extension {
+        Extension::FeeCurve { base_bps, max_bps, target_volume, recipient } => {
+            contract.set_fee_curve(Some(fee_curve(base_bps, max_bps, target_volume, recipient)))
+        }
+        Extension::BurnSink { sink } => contract.set_burn_sink(Some(account(sink))),
+        Extension::BurnAddress { address } => contract.set_burn_address(account(address), true),
+        Extension::PublicSnapshots => contract.set_public_snapshots(true),
+        Extension::AllowanceSnapshots => contract.set_allowance_snapshots(true),
+        Extension::Snapshot => contract.snapshot().map(|_| ()),
+        Extension::LargeMintThreshold { threshold } => contract.set_large_mint_threshold(Some(threshold)),
+        Extension::Timelock { delay } => contract.set_timelock_delay(Some(delay.into())),
+        Extension::Governor { voting_period, proposal_threshold, quorum } => {
+            contract.set_governor_config(Some(GovernorConfig {
+                voting_delay: 0,
+                voting_period,
+                proposal_threshold,
+                quorum
+            }))
+        }
+        Extension::Paused => contract.pause(),
+    };
+}
+
+fn dispatch(contract: &mut Erc20, now: &mut u64, message: Message) {
+    // Errors are expected outcomes here, only panics are findings.
+    let _ = match message {
+        Message::Transfer { to, value } => standard(contract.transfer(account(to), value, Vec::new())),
+        Message::TransferUnchecked { to, value } => contract.transfer_unchecked(account(to), value),
+        Message::BatchTransfer { legs } => {
+            contract.batch_transfer(legs.into_iter().map(|(to, value)| (account(to), value)).collect())
+        }
+        Message::BatchTransferFrom { from, legs } => contract.batch_transfer_from(
+            account(from),
+            legs.into_iter().map(|(to, value)| (account(to), value)).collect()
+        ),
+        Message::Approve { spender, value } => standard(contract.approve(account(spender), value)),
+        Message::IncreaseAllowance { spender, delta } => standard(contract.increase_allowance(account(spender), delta)),
+        Message::DecreaseAllowance { spender, delta } => standard(contract.decrease_allowance(account(spender), delta)),
+        Message::TransferFrom { from, to, value } => {
+            standard(contract.transfer_from(account(from), account(to), value, Vec::new()))
+        }
+        Message::SubApprove { owner, delegate, value } => {
+            contract.sub_approve(account(owner), account(delegate), value)
+        }
+        Message::SetSubDelegationAllowed { allowed } => contract.set_sub_delegation_allowed(allowed),
+        Message::TransferWithDeadline { to, value, deadline } => {
+            contract.transfer_with_deadline(account(to), value, deadline)
+        }
+        Message::TransferWithIdempotencyKey { to, value, key } => {
+            contract.transfer_with_idempotency_key(account(to), value, Hash::from([key; 32]))
+        }
+        Message::SetRecipientGuard { threshold } => contract.set_recipient_guard(threshold),
+        Message::RegisterRecipient { recipient } => contract.register_recipient(account(recipient)),
+        Message::Burn { value } => contract.burn(value),
+        Message::BurnFrom { from, value } => contract.burn_from(account(from), value),
+        Message::SetBurnAddress { address, enabled } => contract.set_burn_address(account(address), enabled),
+        Message::Pause => contract.pause(),
+        Message::Unpause => contract.unpause(),
+        Message::SetFeeCurve { base_bps, max_bps, target_volume, recipient } => {
+            contract.set_fee_curve(Some(fee_curve(base_bps, max_bps, target_volume, recipient)))
+        }
+        Message::SetPairFees { pair, fees } => contract.set_pair_fees(
+            account(pair),
+            fees.map(|(buy_bps, sell_bps)| PairFees { buy_bps, sell_bps })
+        ),
+        Message::SetFeeDiscountTiers { tiers } => contract.set_fee_discount_tiers(
+            tiers
+                .into_iter()
+                .map(|(min_balance, discount_bps)| FeeDiscountTier { min_balance, discount_bps })
+                .collect()
+        ),
+        Message::Mint { to, value } => contract.mint(account(to), value),
+        Message::Airdrop { airdrop_id, cursor, legs, from_treasury } => {
+            let legs = legs.into_iter().map(|(to, value)| (account(to), value, None)).collect();
+            contract.airdrop(airdrop_id.into(), cursor.into(), legs, from_treasury).map(|_| ())
+        }
+        Message::ScheduleMint { to, value, delay } => contract
+            .schedule(TimelockOperation::Mint { to: account(to), value }, delay.into())
+            .map(|_| ()),
+        Message::ExecuteScheduled { operation_id } => contract.execute_scheduled(operation_id.into()),
+        Message::TransferExactOut { to, net_value } => contract.transfer_exact_out(account(to), net_value).map(|_| ()),
+        Message::SetOperator { operator, approved } => contract.set_operator(account(operator), approved),
+        Message::OperatorTransfer { from, to, value } => {
+            contract.operator_transfer(account(from), account(to), value)
+        }
+        Message::Snapshot => contract.snapshot().map(|_| ()),
+        Message::AttestSubBalances { snapshot_id, total } => {
+            contract.attest_sub_balances(snapshot_id.into(), Hash::default(), total)
+        }
+        Message::CreateMandate { merchant, max_per_charge, period } => {
+            contract.create_mandate(account(merchant), max_per_charge, period.into()).map(|_| ())
+        }
+        Message::ChargeMandate { mandate_id, value } => contract.charge_mandate(mandate_id.into(), value),
+        Message::RevokeMandate { mandate_id } => contract.revoke_mandate(mandate_id.into()),
+        Message::Delegate { delegatee } => contract.delegate(account(delegatee)),
+        Message::CreateVesting { beneficiary, total, start, cliff, duration, revocable } => contract
+            .create_vesting(account(beneficiary), total, start.into(), cliff.into(), duration.into(), revocable)
+            .map(|_| ()),
+        Message::SetVestingGrantor { grantor, approved } => contract.set_vesting_grantor(account(grantor), approved),
+        Message::Release => contract.release().map(|_| ()),
+        Message::RevokeVesting { schedule_id } => contract.revoke_vesting(schedule_id.into()),
+        Message::CreateStream { recipient, deposit, start, stop } => contract
+            .create_stream(account(recipient), deposit, start.into(), stop.into())
+            .map(|_| ()),
+        Message::CreateWeightedStream { beneficiaries, deposit, start, stop } => {
+            let beneficiaries = beneficiaries.into_iter().map(|(to, weight)| (account(to), weight)).collect();
+            contract
+                .create_weighted_stream(beneficiaries, deposit, start.into(), stop.into())
+                .map(|_| ())
+        }
+        Message::WithdrawFromStream { stream_id, value } => contract.withdraw_from_stream(stream_id.into(), value),
+        Message::CancelStream { stream_id } => contract.cancel_stream(stream_id.into()),
+        Message::TransferStream { stream_id, to } => contract.transfer_stream(stream_id.into(), account(to)),
+        Message::ClaimAll { stream_ids } => contract
+            .claim_all(stream_ids.into_iter().map(Into::into).collect())
+            .map(|_| ()),
+        Message::SetClaimDelegate { delegate } => contract.set_claim_delegate(delegate.map(account)),
+        Message::ClaimAllFor { holder, stream_ids } => contract
+            .claim_all_for(account(holder), stream_ids.into_iter().map(Into::into).collect())
+            .map(|_| ()),
+        Message::SetClaimDeadline { stream_id, deadline } => {
+            contract.set_claim_deadline(ClaimBucket::Stream(stream_id.into()), deadline.map(Into::into))
+        }
+        Message::SweepUnclaimed { stream_id } => {
+            contract.sweep_unclaimed(ClaimBucket::Stream(stream_id.into())).map(|_| ())
+        }
+        Message::CreateEscrow { payee, arbiter, amount } => {
+            contract.create_escrow(account(payee), account(arbiter), amount).map(|_| ())
+        }
+        Message::ReleaseEscrow { escrow_id } => contract.release_escrow(escrow_id.into()),
+        Message::RefundEscrow { escrow_id } => contract.refund_escrow(escrow_id.into()),
+        Message::Propose => contract.propose(Vec::new(), Hash::default()).map(|_| ()),
+        Message::CastVote { proposal_id, support } => {
+            let support = match support % 3 {
+                0 => VoteType::Against,
+                1 => VoteType::For,
+                _ => VoteType::Abstain
+            };
+            contract.cast_vote(proposal_id.into(), support)
+        }
+        Message::Execute { proposal_id } => contract.execute(proposal_id.into()),
+        Message::AdvanceTime { millis } => {
+            *now = now.saturating_add(millis.into());
+            test::set_block_timestamp::<DefaultEnvironment>(*now);
+            Ok(())
+        }
+    };
+}
+
+/// Lines the standard messages, which fail with a `PSP22Error`, up with the
+/// rest. `dispatch` drops every outcome, so nothing is lost.
+fn standard(result: Result<(), PSP22Error>) -> Result<(), Error> {
+    let _ = result;
+    Ok(())
+}
+
+fn assert_invariants(contract: &Erc20) {
+    let accounts = || (0..ACCOUNTS).map(account);
+
+    // The off-chain contract account is alice's, `account(1)`, so tokens the
+    // contract holds in escrow are part of the sum.
+    let held: u128 = accounts().map(|account| contract.balance_of(account)).sum();
+    assert_eq!(held, contract.total_supply(), "balances no longer add up to the total supply");
+    if let Some(cap) = contract.cap() {
+        assert!(contract.total_supply() <= cap, "total supply went past the cap");
+    }
+
+    let votes: u128 = accounts().map(|account| contract.get_votes(account)).sum();
+    let delegated: u128 = accounts()
+        .filter(|account| contract.delegates(*account).is_some())
+        .map(|account| contract.balance_of(account))
+        .sum();
+    assert_eq!(votes, delegated, "votes no longer add up to the delegated balances");
+
+    let snapshot_id = contract.current_snapshot_id();
+    if snapshot_id != 0 {
+        let held: u128 = accounts()
+            .map(|account| contract.balance_of_at(account, snapshot_id).unwrap())
+            .sum();
+        assert_eq!(
+            Ok(held),
+            contract.total_supply_at(snapshot_id),
+            "snapshot balances no longer add up to the snapshot supply"
+        );
+    }
+}
+
+fuzz_target!(|input: Input| {
+    test::run_test::<DefaultEnvironment, _>(|_| {
+        test::set_caller::<DefaultEnvironment>(account(0));
+        if input.cap.is_some_and(|cap| input.initial_supply > cap) {
+            return Ok(())
+        }
+        let mut contract = Erc20::new(input.initial_supply, None, None, 18, input.cap, Hash::default());
+        for extension in input.extensions {
+            enable(&mut contract, extension);
+        }
+        let mut now = 0;
+
+        for call in input.calls {
+            test::set_caller::<DefaultEnvironment>(account(call.caller));
+            dispatch(&mut contract, &mut now, call.message);
+            assert_invariants(&contract);
+        }
+        Ok(())
+    })
+    .unwrap();
+});
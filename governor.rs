@@ -0,0 +1,113 @@
+//! Types of the token's on-chain governor: holders with enough delegated
+//! votes propose cross-contract calls, delegates vote on them with their
+//! voting power at the proposal's start block, and passed proposals are
+//! executed by anyone.
+
+use ink::prelude::vec::Vec;
+use ink::primitives::{AccountId, Hash};
+
+use crate::traits::Balance;
+
+pub type BlockNumber = <ink::env::DefaultEnvironment as ink::env::Environment>::BlockNumber;
+
+/// Governor parameters, set by an admin with `set_governor_config`.
+#[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+pub struct GovernorConfig {
+    /// Blocks between a proposal and the start of its vote.
+    pub voting_delay: BlockNumber,
+    /// Blocks the vote stays open.
+    pub voting_period: BlockNumber,
+    /// Votes a proposer needs, as of the block before proposing.
+    pub proposal_threshold: Balance,
+    /// `For` plus `Abstain` votes a proposal needs to pass.
+    pub quorum: Balance
+}
+
+/// A call a proposal makes when executed. `input` is the SCALE encoded
+/// arguments that follow `selector`.
+#[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+pub struct ProposalCall {
+    pub callee: AccountId,
+    pub selector: [u8; 4],
+    pub input: Vec<u8>,
+    pub transferred_value: Balance
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout))]
+pub struct Proposal {
+    pub proposer: AccountId,
+    pub calls: Vec<ProposalCall>,
+    pub description_hash: Hash,
+    /// Votes are weighed as of this block and cast after it.
+    pub vote_start: BlockNumber,
+    /// Last block votes are accepted in.
+    pub vote_end: BlockNumber,
+    pub for_votes: Balance,
+    pub against_votes: Balance,
+    pub abstain_votes: Balance,
+    pub executed: bool
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum VoteType {
+    Against,
+    For,
+    Abstain
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum ProposalState {
+    Pending,
+    Active,
+    Defeated,
+    Succeeded,
+    Executed
+}
+
+impl Proposal {
+    /// Where the proposal stands at block `now` under `quorum`.
+    pub fn state(&self, now: BlockNumber, quorum: Balance) -> ProposalState {
+        if self.executed {
+            ProposalState::Executed
+        } else if now <= self.vote_start {
+            ProposalState::Pending
+        } else if now <= self.vote_end {
+            ProposalState::Active
+        } else if self.for_votes > self.against_votes && self.for_votes.saturating_add(self.abstain_votes) >= quorum {
+            ProposalState::Succeeded
+        } else {
+            ProposalState::Defeated
+        }
+    }
+}
+
+/// Already encoded call arguments, appended to the selector as they are.
+pub(crate) struct RawInput<'a>(pub &'a [u8]);
+
+impl scale::Encode for RawInput<'_> {
+    fn size_hint(&self) -> usize {
+        self.0.len()
+    }
+
+    fn encode_to<T: scale::Output + ?Sized>(&self, dest: &mut T) {
+        dest.write(self.0);
+    }
+}
+
+/// Return value of a call left encoded, everything after its `MessageResult` tag.
+pub(crate) struct RawOutput(pub Vec<u8>);
+
+impl scale::Decode for RawOutput {
+    fn decode<I: scale::Input>(input: &mut I) -> Result<Self, scale::Error> {
+        let mut output = Vec::new();
+        while let Ok(byte) = input.read_byte() {
+            output.push(byte);
+        }
+        Ok(RawOutput(output))
+    }
+}
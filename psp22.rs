@@ -0,0 +1,90 @@
+//! The PSP22 fungible token standard as ink! trait definitions, so other
+//! contracts and tooling can call any implementation through the standard
+//! selectors (`PSP22::transfer`, `PSP22Metadata::token_name`, ...).
+
+use ink::prelude::{string::String, vec::Vec};
+use ink::primitives::AccountId;
+
+use crate::traits::Balance;
+
+/// The errors the standard messages can fail with. Anything an implementation
+/// adds beyond the standard is reported as `Custom`.
+#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum PSP22Error {
+    Custom(String),
+    InsufficientBalance,
+    InsufficientAllowance,
+    ZeroRecipientAddress,
+    ZeroSenderAddress,
+    SafeTransferCheckFailed(String)
+}
+
+#[ink::trait_definition]
+pub trait PSP22 {
+    #[ink(message)]
+    fn total_supply(&self) -> Balance;
+
+    #[ink(message)]
+    fn balance_of(&self, owner: AccountId) -> Balance;
+
+    #[ink(message)]
+    fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance;
+
+    /// Moves `value` from the caller to `to`. `data` is passed on to contract
+    /// recipients as the standard describes.
+    #[ink(message)]
+    fn transfer(&mut self, to: AccountId, value: Balance, data: Vec<u8>) -> Result<(), PSP22Error>;
+
+    /// Moves `value` from `from` to `to`, spending the caller's allowance.
+    #[ink(message)]
+    fn transfer_from(&mut self, from: AccountId, to: AccountId, value: Balance, data: Vec<u8>) -> Result<(), PSP22Error>;
+
+    #[ink(message)]
+    fn approve(&mut self, spender: AccountId, value: Balance) -> Result<(), PSP22Error>;
+
+    /// Raises the caller's allowance for `spender` by `delta_value`, saturating
+    /// at the maximum. Unlike `approve` it can't race a pending `transfer_from`.
+    #[ink(message)]
+    fn increase_allowance(&mut self, spender: AccountId, delta_value: Balance) -> Result<(), PSP22Error>;
+
+    /// Lowers the caller's allowance for `spender` by `delta_value`, failing with
+    /// `PSP22Error::InsufficientAllowance` if less than that is left.
+    #[ink(message)]
+    fn decrease_allowance(&mut self, spender: AccountId, delta_value: Balance) -> Result<(), PSP22Error>;
+}
+
+#[ink::trait_definition]
+pub trait PSP22Metadata {
+    #[ink(message)]
+    fn token_name(&self) -> Option<String>;
+
+    #[ink(message)]
+    fn token_symbol(&self) -> Option<String>;
+
+    #[ink(message)]
+    fn token_decimals(&self) -> u8;
+}
+
+/// Why a `PSP22Receiver` refused a transfer.
+#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum PSP22ReceiverError {
+    TransferRejected(String)
+}
+
+/// Implemented by contracts that can hold the token. Transfers to a contract
+/// that doesn't implement it fail, since the tokens would otherwise be stuck.
+#[ink::trait_definition]
+pub trait PSP22Receiver {
+    /// Called before `value` sent by `operator` on behalf of `from` is credited
+    /// to the receiver. Returning an error reverts the transfer.
+    #[ink(message)]
+    fn before_received(
+        &mut self,
+        operator: AccountId,
+        from: AccountId,
+        value: Balance,
+        data: Vec<u8>
+    ) -> Result<(), PSP22ReceiverError>;
+}
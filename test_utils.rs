@@ -0,0 +1,132 @@
+//! Off-chain test helpers for projects that integrate this token, so their
+//! test suites don't have to reimplement the ink! test plumbing.
+//!
+//! Enabled with the `test-utils` feature, and always built for the unit tests;
+//! everything here runs against the off-chain environment of `#[ink::test]`.
+
+use crate::psp22::PSP22;
+use crate::{ecdsa_account, Erc20};
+use ink::env::{test, DefaultEnvironment};
+use ink::primitives::{AccountId, Hash};
+
+pub use crate::erc20::{AccountMetadataUpdated, Approval, SuspiciousActivity, Transfer};
+
+type Balance = <DefaultEnvironment as ink::env::Environment>::Balance;
+type Timestamp = <DefaultEnvironment as ink::env::Environment>::Timestamp;
+
+/// Every event the contract can emit, as decoded from the off-chain event log.
+pub type Event = <Erc20 as ink::reflect::ContractEventBase>::Type;
+
+/// The well-known test accounts; `alice` is the caller that deploys by default.
+pub fn accounts() -> test::DefaultAccounts<DefaultEnvironment> {
+    test::default_accounts::<DefaultEnvironment>()
+}
+
+/// Makes `account` the caller of subsequent messages.
+pub fn set_caller(account: AccountId) {
+    test::set_caller::<DefaultEnvironment>(account);
+}
+
+/// Deploys the token with `initial_supply` owned by `deployer`.
+pub fn deploy(deployer: AccountId, initial_supply: Balance) -> Erc20 {
+    set_caller(deployer);
+    Erc20::new(initial_supply, None, None, 18, None, Hash::default())
+}
+
+/// Moves `value` from `from` to `to`, leaving `from` as the caller.
+///
+/// Panics if the transfer fails, since a failing fixture is a broken test.
+pub fn fund(contract: &mut Erc20, from: AccountId, to: AccountId, value: Balance) {
+    set_caller(from);
+    contract
+        .transfer(to, value, Vec::new())
+        .unwrap_or_else(|err| panic!("funding {to:?} with {value} failed: {err:?}"));
+}
+
+/// All events emitted so far, decoded into their typed structs.
+pub fn emitted_events() -> Vec<Event> {
+    test::recorded_events()
+        .map(|event| {
+            <Event as scale::Decode>::decode(&mut &event.data[..])
+                .unwrap_or_else(|err| panic!("undecodable event: {err}"))
+        })
+        .collect()
+}
+
+/// The `Transfer` events emitted so far, in order.
+pub fn transfer_events() -> Vec<Transfer> {
+    emitted_events()
+        .into_iter()
+        .filter_map(|event| match event {
+            Event::Transfer(transfer) => Some(transfer),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The `Approval` events emitted so far, in order.
+pub fn approval_events() -> Vec<Approval> {
+    emitted_events()
+        .into_iter()
+        .filter_map(|event| match event {
+            Event::Approval(approval) => Some(approval),
+            _ => None,
+        })
+        .collect()
+}
+
+/// An ECDSA signer made from `secret_bytes`, with the account the contract
+/// recovers its signatures to, for the `permit` and `*_with_signature` messages.
+pub fn ecdsa_signer(secret_bytes: [u8; 32]) -> (secp256k1::SecretKey, AccountId) {
+    let secret = secp256k1::SecretKey::from_slice(&secret_bytes).expect("not a valid secret key");
+    let public_key = secret.public_key(&secp256k1::Secp256k1::new()).serialize();
+    (secret, ecdsa_account(&public_key))
+}
+
+/// Signs `hash` the way `ecdsa_recover` expects: compact signature followed
+/// by the recovery id.
+pub fn ecdsa_sign(secret: &secp256k1::SecretKey, hash: [u8; 32]) -> [u8; 65] {
+    let message = secp256k1::Message::from_slice(&hash).expect("hashes are 32 bytes");
+    let (recovery_id, compact) = secp256k1::Secp256k1::new()
+        .sign_ecdsa_recoverable(&message, secret)
+        .serialize_compact();
+    let mut signature = [0u8; 65];
+    signature[..64].copy_from_slice(&compact);
+    signature[64] = recovery_id.to_i32() as u8;
+    signature
+}
+
+/// The bytes `owner` signs for `permit`, hashed with BLAKE2-256 first: the
+/// SCALE encoding of the message tag, the deployment's address and genesis
+/// hash, and the permit itself. Built without a contract, so wallets can check
+/// their own encoding against it; `permit_hash` is the hash of this.
+pub fn permit_payload(
+    contract: AccountId,
+    genesis_hash: Hash,
+    owner: AccountId,
+    spender: AccountId,
+    value: Balance,
+    nonce: u64,
+    deadline: Timestamp,
+) -> Vec<u8> {
+    scale::Encode::encode(&(&b"erc20/permit"[..], contract, genesis_hash, (owner, spender, value, nonce, deadline)))
+}
+
+/// `permit_payload` for `permit_multi`.
+pub fn permit_multi_payload(
+    contract: AccountId,
+    genesis_hash: Hash,
+    owner: AccountId,
+    spenders: Vec<(AccountId, Balance)>,
+    nonce: u64,
+    deadline: Timestamp,
+) -> Vec<u8> {
+    scale::Encode::encode(&(&b"erc20/permit_multi"[..], contract, genesis_hash, (owner, spenders, nonce, deadline)))
+}
+
+/// The BLAKE2-256 hash of a signing payload, the message that is signed.
+pub fn payload_hash(payload: &[u8]) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    ink::env::hash_bytes::<ink::env::hash::Blake2x256>(payload, &mut hash);
+    hash
+}
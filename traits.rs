@@ -0,0 +1,27 @@
+//! Interfaces other contracts implement to plug into the token.
+
+use ink::prelude::vec::Vec;
+use ink::primitives::AccountId;
+
+pub type Balance = <ink::env::DefaultEnvironment as ink::env::Environment>::Balance;
+pub type Timestamp = <ink::env::DefaultEnvironment as ink::env::Environment>::Timestamp;
+
+/// Contract an account registers to vet its own outbound transfers, e.g. to
+/// enforce daily limits or 2FA-style approvals.
+#[ink::trait_definition]
+pub trait TransferGuard {
+    /// Returns whether `owner` may send `value` to `to`. Called by the token
+    /// before every outbound transfer of an account that registered this guard.
+    #[ink(message)]
+    fn check_transfer(&mut self, owner: AccountId, to: AccountId, value: Balance) -> bool;
+}
+
+/// Contract that wants to react to tokens sent to it with `transfer_and_call`,
+/// e.g. to credit a deposit or pay for a service in the same transaction.
+#[ink::trait_definition]
+pub trait TokenReceiver {
+    /// Called after `value` was moved from `from` to the receiver. Returning
+    /// `false` rejects the tokens and reverts the transfer.
+    #[ink(message)]
+    fn on_token_transfer(&mut self, from: AccountId, value: Balance, data: Vec<u8>) -> bool;
+}